@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+use std::fs::File;
 use std::io;
+use std::io::BufWriter;
 use std::time::{Duration, Instant};
 
 use anyhow::{bail, Context, Result};
@@ -11,8 +14,15 @@ const MAX_DATAGRAM_SIZE: usize = 1350;
 #[derive(Parser)]
 #[command(about = "HTTP/3 benchmark client for QLOG overhead measurement")]
 struct Args {
-    /// Server URL (e.g., https://10.20.0.10/small)
-    url: String,
+    /// Server URL (e.g., https://10.20.0.10/small); may be repeated to
+    /// round-robin across multiple targets over the same connection
+    #[arg(short = 'u', long = "url", required = true)]
+    urls: Vec<String>,
+
+    /// File with one path per line (e.g. /small), appended to the targets
+    /// cycled through for each request
+    #[arg(long)]
+    urls_file: Option<String>,
 
     /// Number of measured requests
     #[arg(short = 'n', long, default_value_t = 10)]
@@ -22,6 +32,10 @@ struct Args {
     #[arg(short, long, default_value_t = 1)]
     warmup: u32,
 
+    /// Number of requests to keep in flight at once
+    #[arg(short, long, default_value_t = 1)]
+    concurrency: u32,
+
     /// Idle timeout in milliseconds
     #[arg(long, default_value_t = 30_000)]
     idle_timeout: u64,
@@ -33,14 +47,44 @@ struct Args {
     /// Skip TLS certificate verification
     #[arg(long)]
     insecure: bool,
+
+    /// Congestion control algorithm (cubic, reno, or bbr)
+    #[arg(long, default_value = "cubic")]
+    cc: String,
+
+    /// Path to write TLS secrets in NSS key log format (SSLKEYLOGFILE)
+    #[arg(long)]
+    keylog: Option<String>,
+
+    /// Print a latency distribution summary (percentiles, stddev, throughput)
+    #[arg(long)]
+    summary: bool,
+
+    /// Path to persist/load the TLS session ticket for 0-RTT resumption
+    #[arg(long)]
+    session_file: Option<String>,
+
+    /// Directory to write a per-connection qlog file into
+    #[arg(long)]
+    qlog_dir: Option<String>,
+
+    /// Title recorded in the qlog trace
+    #[arg(long, default_value = "nginx-qlog-benchmark")]
+    qlog_title: String,
+
+    /// Qlog verbosity (core, base, or extra)
+    #[arg(long, default_value = "extra")]
+    qlog_level: String,
 }
 
 struct RequestResult {
     index: u32,
+    path: String,
     status: u16,
     ttfb: Duration,
     total_time: Duration,
     bytes_received: u64,
+    early_data: bool,
 }
 
 struct InflightRequest {
@@ -48,12 +92,35 @@ struct InflightRequest {
     first_byte: Option<Instant>,
     status: u16,
     bytes_received: u64,
-    stream_id: u64,
+    path: String,
+    early_data: bool,
+}
+
+/// A request target: a `:path` value paired with its pre-built headers.
+struct Target {
+    path: String,
+    headers: Vec<quiche::h3::Header>,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    let url = url::Url::parse(&args.url).context("invalid URL")?;
+    let url = url::Url::parse(&args.urls[0]).context("invalid URL")?;
+
+    let qlog_level = match args.qlog_level.as_str() {
+        "core" => quiche::QlogLevel::Core,
+        "base" => quiche::QlogLevel::Base,
+        "extra" => quiche::QlogLevel::Extra,
+        other => bail!("invalid --qlog-level: {other} (expected core, base, or extra)"),
+    };
+
+    match args.cc.as_str() {
+        "cubic" | "reno" | "bbr" => (),
+        other => bail!("invalid --cc: {other} (expected cubic, reno, or bbr)"),
+    }
+
+    if args.concurrency < 1 {
+        bail!("invalid --concurrency: {} (must be at least 1)", args.concurrency);
+    }
 
     let peer_addr = url
         .socket_addrs(|| Some(443))
@@ -62,23 +129,36 @@ fn main() -> Result<()> {
         .next()
         .context("no addresses resolved")?;
 
-    // Prepare request path.
-    let mut path = String::from(url.path());
-    if let Some(query) = url.query() {
-        path.push('?');
-        path.push_str(query);
+    // Build the set of request targets to cycle through: one per --url plus
+    // one per line of --urls-file (each a bare path on the same endpoint).
+    let mut targets: Vec<Target> = Vec::new();
+    for raw in &args.urls {
+        let target_url = url::Url::parse(raw).context("invalid URL")?;
+        if target_url.scheme() != url.scheme()
+            || target_url.host_str() != url.host_str()
+            || target_url.port_or_known_default() != url.port_or_known_default()
+        {
+            bail!(
+                "--url {raw} targets a different endpoint than the first --url \
+                 ({}://{}); all --url values must share the same scheme and authority",
+                url.scheme(),
+                url.host_str().unwrap_or("localhost"),
+            );
+        }
+        targets.push(make_target(&url, &request_path(&target_url)));
     }
 
-    let req_headers = vec![
-        quiche::h3::Header::new(b":method", b"GET"),
-        quiche::h3::Header::new(b":scheme", url.scheme().as_bytes()),
-        quiche::h3::Header::new(
-            b":authority",
-            url.host_str().unwrap_or("localhost").as_bytes(),
-        ),
-        quiche::h3::Header::new(b":path", path.as_bytes()),
-        quiche::h3::Header::new(b"user-agent", b"benchmark-client"),
-    ];
+    if let Some(ref file) = args.urls_file {
+        let contents = std::fs::read_to_string(file)
+            .with_context(|| format!("failed to read --urls-file {file}"))?;
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            targets.push(make_target(&url, line));
+        }
+    }
 
     // Setup event loop.
     let mut poll = mio::Poll::new()?;
@@ -118,6 +198,17 @@ fn main() -> Result<()> {
     config.set_initial_max_streams_bidi(1_000);
     config.set_initial_max_streams_uni(100);
     config.set_disable_active_migration(true);
+    config
+        .set_cc_algorithm_name(&args.cc)
+        .context("failed to set congestion control algorithm")?;
+
+    if args.session_file.is_some() {
+        config.enable_early_data();
+    }
+
+    if args.keylog.is_some() {
+        config.log_keys();
+    }
 
     // Create QUIC connection.
     let mut scid = [0; quiche::MAX_CONN_ID_LEN];
@@ -130,6 +221,38 @@ fn main() -> Result<()> {
         quiche::connect(url.domain(), &scid, local_addr, peer_addr, &mut config)
             .context("QUIC connect failed")?;
 
+    if let Some(ref session_file) = args.session_file {
+        if let Ok(session) = std::fs::read(session_file) {
+            conn.set_session(&session).context("failed to set session")?;
+        }
+    }
+
+    if let Some(ref keylog_path) = args.keylog {
+        let file = File::create(keylog_path)
+            .with_context(|| format!("failed to create keylog file {keylog_path}"))?;
+        conn.set_keylog(Box::new(file));
+    }
+
+    let qlog_path = if let Some(ref dir) = args.qlog_dir {
+        let id = hex_dump(&scid);
+        let mut path = std::path::PathBuf::from(dir);
+        path.push(format!("{id}.sqlog"));
+
+        let file = File::create(&path)
+            .with_context(|| format!("failed to create qlog file {path:?}"))?;
+
+        conn.set_qlog_with_level(
+            Box::new(BufWriter::new(file)),
+            args.qlog_title.clone(),
+            format!("{} id={}", args.qlog_title, id),
+            qlog_level,
+        );
+
+        Some(path)
+    } else {
+        None
+    };
+
     // Send initial handshake packet.
     let mut out = [0; MAX_DATAGRAM_SIZE];
     let (write, send_info) = conn.send(&mut out).context("initial send failed")?;
@@ -143,7 +266,9 @@ fn main() -> Result<()> {
     let mut requests_sent: u32 = 0;
     let mut requests_done: u32 = 0;
     let mut results: Vec<RequestResult> = Vec::with_capacity(args.requests as usize);
-    let mut inflight: Option<InflightRequest> = None;
+    let mut inflight: HashMap<u64, InflightRequest> = HashMap::new();
+    let mut measured_start: Option<Instant> = None;
+    let mut measured_end: Option<Instant> = None;
 
     loop {
         poll.poll(&mut events, conn.timeout())?;
@@ -175,28 +300,41 @@ fn main() -> Result<()> {
             break;
         }
 
-        // Create HTTP/3 connection once QUIC handshake completes.
-        if conn.is_established() && h3_conn.is_none() {
+        // Create HTTP/3 connection once the handshake completes, or as soon
+        // as 0-RTT is available so early-data requests can actually be sent.
+        if (conn.is_established() || conn.is_in_early_data()) && h3_conn.is_none() {
             h3_conn = Some(
                 quiche::h3::Connection::with_transport(&mut conn, &h3_config)
                     .context("failed to create HTTP/3 connection")?,
             );
         }
 
-        // Send next request if nothing in-flight and requests remaining.
+        // Open new request streams up to the concurrency window, cycling
+        // through the configured targets.
         if let Some(h3) = &mut h3_conn {
-            if inflight.is_none() && requests_sent < total_requests {
+            while requests_sent < total_requests
+                && inflight.len() < args.concurrency as usize
+            {
+                let target = &targets[requests_sent as usize % targets.len()];
                 let stream_id = h3
-                    .send_request(&mut conn, &req_headers, true)
+                    .send_request(&mut conn, &target.headers, true)
                     .context("send_request failed")?;
 
-                inflight = Some(InflightRequest {
-                    start: Instant::now(),
-                    first_byte: None,
-                    status: 0,
-                    bytes_received: 0,
+                if requests_sent == args.warmup {
+                    measured_start = Some(Instant::now());
+                }
+
+                inflight.insert(
                     stream_id,
-                });
+                    InflightRequest {
+                        start: Instant::now(),
+                        first_byte: None,
+                        status: 0,
+                        bytes_received: 0,
+                        path: target.path.clone(),
+                        early_data: conn.is_in_early_data(),
+                    },
+                );
                 requests_sent += 1;
             }
         }
@@ -206,53 +344,50 @@ fn main() -> Result<()> {
             loop {
                 match h3.poll(&mut conn) {
                     Ok((stream_id, quiche::h3::Event::Headers { list, .. })) => {
-                        if let Some(ref mut req) = inflight {
-                            if stream_id == req.stream_id {
-                                req.first_byte = Some(Instant::now());
-                                for hdr in &list {
-                                    if hdr.name() == b":status" {
-                                        req.status = std::str::from_utf8(hdr.value())
-                                            .unwrap_or("0")
-                                            .parse()
-                                            .unwrap_or(0);
-                                    }
+                        if let Some(req) = inflight.get_mut(&stream_id) {
+                            req.first_byte = Some(Instant::now());
+                            for hdr in &list {
+                                if hdr.name() == b":status" {
+                                    req.status = std::str::from_utf8(hdr.value())
+                                        .unwrap_or("0")
+                                        .parse()
+                                        .unwrap_or(0);
                                 }
                             }
                         }
                     }
 
                     Ok((stream_id, quiche::h3::Event::Data)) => {
-                        if let Some(ref mut req) = inflight {
-                            if stream_id == req.stream_id {
-                                while let Ok(read) =
-                                    h3.recv_body(&mut conn, stream_id, &mut buf)
-                                {
-                                    req.bytes_received += read as u64;
-                                }
+                        if let Some(req) = inflight.get_mut(&stream_id) {
+                            while let Ok(read) =
+                                h3.recv_body(&mut conn, stream_id, &mut buf)
+                            {
+                                req.bytes_received += read as u64;
                             }
                         }
                     }
 
                     Ok((stream_id, quiche::h3::Event::Finished)) => {
-                        if let Some(req) = inflight.take() {
-                            if stream_id == req.stream_id {
-                                let now = Instant::now();
-
-                                // Only record results after warmup.
-                                if requests_done >= args.warmup {
-                                    results.push(RequestResult {
-                                        index: requests_done - args.warmup,
-                                        status: req.status,
-                                        ttfb: req
-                                            .first_byte
-                                            .unwrap_or(now)
-                                            .duration_since(req.start),
-                                        total_time: now.duration_since(req.start),
-                                        bytes_received: req.bytes_received,
-                                    });
-                                }
-                                requests_done += 1;
+                        if let Some(req) = inflight.remove(&stream_id) {
+                            let now = Instant::now();
+
+                            // Only record results after warmup.
+                            if requests_done >= args.warmup {
+                                results.push(RequestResult {
+                                    index: requests_done - args.warmup,
+                                    path: req.path,
+                                    status: req.status,
+                                    ttfb: req
+                                        .first_byte
+                                        .unwrap_or(now)
+                                        .duration_since(req.start),
+                                    total_time: now.duration_since(req.start),
+                                    bytes_received: req.bytes_received,
+                                    early_data: req.early_data,
+                                });
+                                measured_end = Some(now);
                             }
+                            requests_done += 1;
                         }
 
                         // All requests done â€” close connection.
@@ -261,9 +396,9 @@ fn main() -> Result<()> {
                         }
                     }
 
-                    Ok((_stream_id, quiche::h3::Event::Reset(e))) => {
+                    Ok((stream_id, quiche::h3::Event::Reset(e))) => {
                         eprintln!("stream reset by peer: {e}");
-                        inflight = None;
+                        inflight.remove(&stream_id);
                         requests_done += 1;
 
                         if requests_done >= total_requests {
@@ -307,28 +442,60 @@ fn main() -> Result<()> {
         }
     }
 
+    if let Some(ref session_file) = args.session_file {
+        if let Some(session) = conn.session() {
+            std::fs::write(session_file, session)
+                .with_context(|| format!("failed to write session file {session_file}"))?;
+        }
+    }
+
     // Print CSV results to stdout.
-    println!("index,status,ttfb_ms,total_time_ms,bytes");
+    println!("index,path,status,ttfb_ms,total_time_ms,bytes,early_data");
     for r in &results {
         println!(
-            "{},{},{:.3},{:.3},{}",
+            "{},{},{},{:.3},{:.3},{},{}",
             r.index,
+            r.path,
             r.status,
             r.ttfb.as_secs_f64() * 1000.0,
             r.total_time.as_secs_f64() * 1000.0,
             r.bytes_received,
+            r.early_data,
         );
     }
 
     // Print summary to stderr.
     let stats = conn.stats();
     let path_stats = conn.path_stats().next();
+
+    // Drop the connection (and its qlog BufWriter) so buffered qlog events
+    // are flushed to disk before we read the file size below.
+    drop(conn);
+
     eprintln!("---");
-    eprintln!("endpoint: {}", args.url);
     eprintln!(
-        "requests: {} (+ {} warmup)",
-        args.requests, args.warmup
+        "endpoint: {}://{} ({} target(s))",
+        url.scheme(),
+        url.host_str().unwrap_or("localhost"),
+        targets.len(),
     );
+    eprintln!(
+        "requests: {} (+ {} warmup, concurrency {}, cc {})",
+        args.requests, args.warmup, args.concurrency, args.cc
+    );
+    let early_data_count = results.iter().filter(|r| r.early_data).count();
+    if early_data_count > 0 {
+        eprintln!("0-RTT: {early_data_count}/{} requests", results.len());
+    }
+    if let (Some(start), Some(end)) = (measured_start, measured_end) {
+        let elapsed = end.duration_since(start).as_secs_f64();
+        if elapsed > 0.0 {
+            eprintln!(
+                "throughput: {:.2} req/s",
+                results.len() as f64 / elapsed
+            );
+        }
+    }
     if let Some(ps) = path_stats {
         eprintln!(
             "rtt: {:.3}ms (min: {:.3}ms)",
@@ -344,10 +511,100 @@ fn main() -> Result<()> {
         "bytes: sent={} recv={}",
         stats.sent_bytes, stats.recv_bytes,
     );
+    if let Some(path) = qlog_path {
+        let qlog_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        eprintln!("qlog: {path:?} ({qlog_bytes} bytes)");
+    }
+
+    if args.summary {
+        eprintln!("---");
+        if results.is_empty() {
+            eprintln!("no samples");
+        } else {
+            summarize_latency("ttfb", results.iter().map(|r| r.ttfb));
+            summarize_latency("total_time", results.iter().map(|r| r.total_time));
+
+            if let (Some(start), Some(end)) = (measured_start, measured_end) {
+                let elapsed = end.duration_since(start).as_secs_f64();
+                let total_bytes: u64 = results.iter().map(|r| r.bytes_received).sum();
+                if elapsed > 0.0 {
+                    eprintln!(
+                        "throughput: {:.2} bytes/sec",
+                        total_bytes as f64 / elapsed
+                    );
+                }
+            }
+        }
+    }
 
     Ok(())
 }
 
+/// Prints min/mean/p50/p90/p99/max and standard deviation for a latency
+/// metric, in milliseconds.
+fn summarize_latency(label: &str, durations: impl Iterator<Item = Duration>) {
+    let mut ms: Vec<f64> = durations.map(|d| d.as_secs_f64() * 1000.0).collect();
+    ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let n = ms.len();
+    let mean = ms.iter().sum::<f64>() / n as f64;
+    let variance = ms.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n as f64;
+
+    eprintln!(
+        "{label}: min={:.3}ms mean={mean:.3}ms p50={:.3}ms p90={:.3}ms p99={:.3}ms max={:.3}ms stddev={:.3}ms",
+        ms[0],
+        percentile(&ms, 50.0),
+        percentile(&ms, 90.0),
+        percentile(&ms, 99.0),
+        ms[n - 1],
+        variance.sqrt(),
+    );
+}
+
+/// Nearest-rank percentile: index `ceil(p/100 * n) - 1`, clamped to
+/// `[0, n - 1]`. `sorted` must be sorted ascending and non-empty.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let n = sorted.len() as f64;
+    let idx = ((p / 100.0 * n).ceil() as isize - 1).clamp(0, sorted.len() as isize - 1);
+    sorted[idx as usize]
+}
+
+/// Extracts the `:path` value (path + query) from a parsed URL.
+fn request_path(url: &url::Url) -> String {
+    let mut path = String::from(url.path());
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    path
+}
+
+/// Builds a request target against `base_url`'s scheme/authority for the
+/// given `:path`.
+fn make_target(base_url: &url::Url, path: &str) -> Target {
+    Target {
+        path: path.to_string(),
+        headers: vec![
+            quiche::h3::Header::new(b":method", b"GET"),
+            quiche::h3::Header::new(b":scheme", base_url.scheme().as_bytes()),
+            quiche::h3::Header::new(
+                b":authority",
+                base_url.host_str().unwrap_or("localhost").as_bytes(),
+            ),
+            quiche::h3::Header::new(b":path", path.as_bytes()),
+            quiche::h3::Header::new(b"user-agent", b"benchmark-client"),
+        ],
+    }
+}
+
+fn hex_dump(buf: &[u8]) -> String {
+    let mut s = String::with_capacity(buf.len() * 2);
+    for b in buf {
+        s.push_str(&format!("{b:02x}"));
+    }
+    s
+}
+
 fn send_to(
     socket: &mio::net::UdpSocket,
     buf: &[u8],